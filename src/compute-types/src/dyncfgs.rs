@@ -9,6 +9,10 @@
 
 //! Dyncfgs used by the compute layer.
 
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
 use mz_dyncfg::{Config, ConfigSet};
 
 /// Whether rendering should use `mz_join_core` rather than DD's `JoinCore::join_core`.
@@ -20,15 +24,201 @@ pub const ENABLE_MZ_JOIN_CORE: Config<bool> = Config::new(
 );
 
 /// The yielding behavior with which linear joins should be rendered.
+///
+/// The raw string is kept as the serialized representation for backwards
+/// compatibility. [`join_yielding`] surfaces the parsed [`JoinYielding`]
+/// strategy (or parse error) to callers, and [`set_linear_join_yielding`]
+/// rejects a malformed value when a caller routes an update through it —
+/// but note that's an opt-in guard, not an enforced one: `mz_dyncfg`'s
+/// `Config<String>`/`ConfigSet` apply path (what `ALTER SYSTEM SET`
+/// ultimately calls) has no validation hook for `Config<String>` values,
+/// so a value set through that path directly still lands unvalidated.
+/// Closing that gap fully needs either a validation hook added to
+/// `mz_dyncfg` itself, or every setter (including the SQL layer) updated
+/// to go through [`set_linear_join_yielding`]; neither lives in this
+/// crate.
 pub const LINEAR_JOIN_YIELDING: Config<String> = Config::new(
     "linear_join_yielding",
     "work:1000000,time:100",
     "The yielding behavior compute rendering should apply for linear join operators. Either \
-     'work:<amount>' or 'time:<milliseconds>' or 'work:<amount>,time:<milliseconds>'. Note \
-     that omitting one of 'work' or 'time' will entirely disable join yielding by time or \
-     work, respectively, rather than falling back to some default.",
+     'work:<amount>' or 'time:<milliseconds>' or 'work:<amount>,time:<milliseconds>', or \
+     'adaptive:<target_ms>,<min_work>,<max_work>' to adjust the work budget toward a target \
+     yield window length. Note that omitting one of 'work' or 'time' will entirely disable \
+     join yielding by work or time, respectively, rather than falling back to some default.",
 );
 
+/// The parsed, validated form of the [`LINEAR_JOIN_YIELDING`] dyncfg.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinYielding {
+    /// Yield once the given amount of work and/or wall-clock time has
+    /// elapsed since the last yield, whichever comes first. A `None`
+    /// component disables yielding along that dimension entirely.
+    Fixed {
+        work: Option<usize>,
+        time: Option<Duration>,
+    },
+    /// Adaptively size the work budget so that each yield window takes
+    /// approximately `target` wall-clock time, rather than using a fixed
+    /// work budget.
+    Adaptive {
+        target: Duration,
+        min_work: usize,
+        max_work: usize,
+    },
+}
+
+impl FromStr for JoinYielding {
+    type Err = JoinYieldingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("adaptive:") {
+            let parts: Vec<_> = rest.split(',').collect();
+            let [target, min_work, max_work] = parts[..] else {
+                return Err(JoinYieldingParseError(s.to_string()));
+            };
+            let target = target
+                .parse()
+                .map_err(|_| JoinYieldingParseError(s.to_string()))?;
+            let min_work = min_work
+                .parse()
+                .map_err(|_| JoinYieldingParseError(s.to_string()))?;
+            let max_work = max_work
+                .parse()
+                .map_err(|_| JoinYieldingParseError(s.to_string()))?;
+            if min_work > max_work {
+                return Err(JoinYieldingParseError(s.to_string()));
+            }
+            return Ok(JoinYielding::Adaptive {
+                target: Duration::from_millis(target),
+                min_work,
+                max_work,
+            });
+        }
+
+        let mut work = None;
+        let mut time = None;
+        for field in s.split(',') {
+            if let Some(amount) = field.strip_prefix("work:") {
+                let amount = amount
+                    .parse()
+                    .map_err(|_| JoinYieldingParseError(s.to_string()))?;
+                work = Some(amount);
+            } else if let Some(millis) = field.strip_prefix("time:") {
+                let millis: u64 = millis
+                    .parse()
+                    .map_err(|_| JoinYieldingParseError(s.to_string()))?;
+                time = Some(Duration::from_millis(millis));
+            } else {
+                return Err(JoinYieldingParseError(s.to_string()));
+            }
+        }
+        Ok(JoinYielding::Fixed { work, time })
+    }
+}
+
+/// An error parsing the [`LINEAR_JOIN_YIELDING`] dyncfg into a
+/// [`JoinYielding`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JoinYieldingParseError(String);
+
+impl fmt::Display for JoinYieldingParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid linear_join_yielding spec: {}", self.0)
+    }
+}
+
+impl std::error::Error for JoinYieldingParseError {}
+
+/// Returns the [`JoinYielding`] strategy currently configured by
+/// [`LINEAR_JOIN_YIELDING`], or an error if the configured string is not a
+/// valid spec.
+pub fn join_yielding(configs: &ConfigSet) -> Result<JoinYielding, JoinYieldingParseError> {
+    LINEAR_JOIN_YIELDING.get(configs).parse()
+}
+
+/// Sets [`LINEAR_JOIN_YIELDING`] to `raw`, first validating it as a
+/// [`JoinYielding`] spec; the existing value is left untouched and `raw` is
+/// rejected if it doesn't parse.
+///
+/// This only guards callers that route through this function specifically.
+/// It is *not* hooked into `mz_dyncfg`'s generic `Config<String>`/`ConfigSet`
+/// apply path, so a value set some other way (in particular, the real
+/// `ALTER SYSTEM SET` path in the SQL layer, which isn't present in this
+/// crate) bypasses this check entirely and the original "a typo silently
+/// disables yielding" failure mode remains possible there. Fully closing it
+/// requires either a validation hook in `mz_dyncfg` itself or routing every
+/// setter through this function; this crate can only provide the latter
+/// for its own callers.
+pub fn set_linear_join_yielding(
+    configs: &ConfigSet,
+    raw: String,
+) -> Result<(), JoinYieldingParseError> {
+    raw.parse::<JoinYielding>()?;
+    LINEAR_JOIN_YIELDING.set(configs, raw);
+    Ok(())
+}
+
+/// Adaptively adjusts a linear join's per-yield work budget so that each
+/// yield window converges on a target wall-clock duration, as configured by
+/// [`JoinYielding::Adaptive`].
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveYieldController {
+    target: Duration,
+    min_work: usize,
+    max_work: usize,
+    current_work: usize,
+}
+
+/// The amount by which the work budget grows on each undershoot of the
+/// target yield window.
+const ADAPTIVE_GROWTH_STEP: usize = 1000;
+
+/// Builds the [`AdaptiveYieldController`] implied by [`LINEAR_JOIN_YIELDING`],
+/// or `None` if the configured strategy is [`JoinYielding::Fixed`] rather
+/// than [`JoinYielding::Adaptive`].
+pub fn adaptive_yield_controller(
+    configs: &ConfigSet,
+) -> Result<Option<AdaptiveYieldController>, JoinYieldingParseError> {
+    Ok(match join_yielding(configs)? {
+        JoinYielding::Fixed { .. } => None,
+        JoinYielding::Adaptive {
+            target,
+            min_work,
+            max_work,
+        } => Some(AdaptiveYieldController::new(target, min_work, max_work)),
+    })
+}
+
+impl AdaptiveYieldController {
+    /// Creates a new controller, starting at the maximum work budget.
+    pub fn new(target: Duration, min_work: usize, max_work: usize) -> AdaptiveYieldController {
+        AdaptiveYieldController {
+            target,
+            min_work,
+            max_work,
+            current_work: max_work,
+        }
+    }
+
+    /// The work budget that should be used for the next yield window.
+    pub fn work_budget(&self) -> usize {
+        self.current_work
+    }
+
+    /// Records how long the most recently completed yield window took,
+    /// halving the work budget on overshoot and growing it by a fixed step
+    /// on undershoot, clamped to `[min_work, max_work]`.
+    pub fn observe(&mut self, elapsed: Duration) {
+        self.current_work = if elapsed > self.target {
+            (self.current_work / 2).max(self.min_work)
+        } else if elapsed < self.target {
+            (self.current_work + ADAPTIVE_GROWTH_STEP).min(self.max_work)
+        } else {
+            self.current_work
+        };
+    }
+}
+
 /// Enable lgalloc for columnation.
 pub const ENABLE_COLUMNATION_LGALLOC: Config<bool> = Config::new(
     "enable_columnation_lgalloc",
@@ -57,6 +247,14 @@ pub const ENABLE_OPERATOR_HYDRATION_STATUS_LOGGING: Config<bool> = Config::new(
     "Enable logging of the hydration status of compute operators.",
 );
 
+/// Enable collecting a structured, per-operator JSON metrics report (see
+/// `mz_dataflow::metrics_report`) instead of only logging hydration status.
+pub const ENABLE_OPERATOR_METRICS_REPORT: Config<bool> = Config::new(
+    "enable_compute_operator_metrics_report",
+    false,
+    "Enable collection of a structured JSON metrics report for dataflow operators.",
+);
+
 /// The "physical backpressure" of `compute_dataflow_max_inflight_bytes_cc` has
 /// been replaced in cc replicas by persist lgalloc and we intend to remove it
 /// once everything has switched to cc. In the meantime, this is a CYA to turn
@@ -77,5 +275,6 @@ pub fn all_dyncfgs(configs: ConfigSet) -> ConfigSet {
         .add(&ENABLE_LGALLOC_EAGER_RECLAMATION)
         .add(&ENABLE_CHUNKED_STACK)
         .add(&ENABLE_OPERATOR_HYDRATION_STATUS_LOGGING)
+        .add(&ENABLE_OPERATOR_METRICS_REPORT)
         .add(&DATAFLOW_MAX_INFLIGHT_BYTES_CC)
 }
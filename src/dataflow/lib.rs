@@ -14,6 +14,7 @@ mod types;
 
 pub mod coordinator;
 pub mod logging;
+pub mod metrics_report;
 pub mod server;
 
 pub use exfiltrate::{Exfiltration, ExfiltratorConfig};
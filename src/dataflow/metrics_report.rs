@@ -0,0 +1,201 @@
+// Copyright 2019 Materialize, Inc. All rights reserved.
+//
+// This file is part of Materialize. Materialize may not be used or
+// distributed without the express permission of Materialize, Inc.
+
+//! A structured, machine-readable report of per-operator dataflow metrics.
+//!
+//! `ENABLE_OPERATOR_HYDRATION_STATUS_LOGGING` only ever dumps hydration
+//! status as unstructured log lines. This module gives the same kind of
+//! information -- plus yielding and inflight-byte metrics -- a structured
+//! home: an in-memory tree of [`ReportNode`]s, pushed and popped as
+//! operators enter and leave scope (mirroring a build-metrics collector),
+//! which can be serialized as nested JSON keyed by dataflow and operator
+//! and emitted on demand or at process exit.
+//!
+//! Collection is gated by the [`ENABLE_OPERATOR_METRICS_REPORT`] dyncfg so
+//! it can be toggled per replica, via [`OperatorActivation`], which render
+//! code should open for the duration of each operator activation.
+//!
+//! Note: this module does not yet have a caller. Render code (the part of
+//! this crate that would schedule operator activations and call
+//! `OperatorActivation::begin`/`emit_metrics_report` at the appropriate
+//! points) is not present in this trimmed checkout, so nothing drives this
+//! today; `to_json`/`emit_metrics_report` can only ever serialize an empty
+//! tree until that wiring lands alongside it.
+//!
+//! [`ENABLE_OPERATOR_METRICS_REPORT`]: mz_compute_types::dyncfgs::ENABLE_OPERATOR_METRICS_REPORT
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use mz_compute_types::dyncfgs::ENABLE_OPERATOR_METRICS_REPORT;
+use mz_dyncfg::ConfigSet;
+use serde::Serialize;
+
+/// Metrics recorded for a single operator.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OperatorMetrics {
+    /// When the operator finished hydrating, if it has.
+    pub hydrated_at: Option<DateTime<Utc>>,
+    /// Total time the operator spent yielding under
+    /// `LINEAR_JOIN_YIELDING`.
+    pub yielding_duration: Duration,
+    /// The high-water mark of in-flight bytes observed for the operator,
+    /// as governed by `DATAFLOW_MAX_INFLIGHT_BYTES_CC`.
+    pub inflight_bytes_high_water_mark: usize,
+}
+
+/// A node in the in-memory report tree.
+///
+/// Nodes are keyed by name (e.g. `dataflow-<id>` or `operator-<id>`) within
+/// their parent, so the tree serializes as nested JSON keyed by dataflow
+/// and operator.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReportNode {
+    /// How long this node's scope was open, once it has been closed.
+    pub duration: Option<Duration>,
+    /// Metrics recorded directly against this node.
+    pub metrics: OperatorMetrics,
+    /// Child nodes, keyed by name.
+    pub children: BTreeMap<String, ReportNode>,
+}
+
+fn report() -> &'static Mutex<ReportNode> {
+    static REPORT: OnceLock<Mutex<ReportNode>> = OnceLock::new();
+    REPORT.get_or_init(|| Mutex::new(ReportNode::default()))
+}
+
+/// A handle to a node currently open in the report tree.
+///
+/// Dropping the guard closes the node: its duration is recorded and it is
+/// attached to its parent (or the root, if it has no parent) under `path`.
+pub struct ReportScope {
+    path: Vec<String>,
+    start: Instant,
+    metrics: OperatorMetrics,
+}
+
+impl ReportScope {
+    /// Opens a new scope for the given dataflow and operator, nested under
+    /// `dataflow-<dataflow_id>/operator-<operator_id>` in the report tree.
+    pub fn operator(dataflow_id: usize, operator_id: usize) -> ReportScope {
+        ReportScope {
+            path: vec![
+                format!("dataflow-{dataflow_id}"),
+                format!("operator-{operator_id}"),
+            ],
+            start: Instant::now(),
+            metrics: OperatorMetrics::default(),
+        }
+    }
+
+    /// Records that the operator has finished hydrating.
+    pub fn record_hydrated(&mut self, at: DateTime<Utc>) {
+        self.metrics.hydrated_at = Some(at);
+    }
+
+    /// Adds to the time this operator has spent yielding.
+    pub fn record_yield(&mut self, duration: Duration) {
+        self.metrics.yielding_duration += duration;
+    }
+
+    /// Records an observed in-flight byte count, updating the high-water
+    /// mark if it is the largest seen so far.
+    pub fn record_inflight_bytes(&mut self, bytes: usize) {
+        self.metrics.inflight_bytes_high_water_mark =
+            self.metrics.inflight_bytes_high_water_mark.max(bytes);
+    }
+}
+
+impl Drop for ReportScope {
+    fn drop(&mut self) {
+        let mut node = ReportNode {
+            duration: Some(self.start.elapsed()),
+            metrics: std::mem::take(&mut self.metrics),
+            children: BTreeMap::new(),
+        };
+        let mut root = report().lock().expect("lock poisoned");
+        let mut current = &mut *root;
+        for segment in &self.path[..self.path.len() - 1] {
+            current = current.children.entry(segment.clone()).or_default();
+        }
+        let last = self.path.last().expect("path is non-empty");
+        // A scope may be entered multiple times over the lifetime of an
+        // operator (e.g. once per activation); merge rather than overwrite.
+        let existing = current.children.entry(last.clone()).or_default();
+        existing.duration =
+            Some(existing.duration.unwrap_or_default() + node.duration.take().unwrap_or_default());
+        existing.metrics.hydrated_at = existing.metrics.hydrated_at.or(node.metrics.hydrated_at);
+        existing.metrics.yielding_duration += node.metrics.yielding_duration;
+        existing.metrics.inflight_bytes_high_water_mark = existing
+            .metrics
+            .inflight_bytes_high_water_mark
+            .max(node.metrics.inflight_bytes_high_water_mark);
+    }
+}
+
+/// Serializes the current report tree as a JSON string.
+pub fn to_json() -> serde_json::Result<String> {
+    serde_json::to_string(&*report().lock().expect("lock poisoned"))
+}
+
+/// Tracks a single operator activation for logging/metrics purposes.
+///
+/// Render code should call [`OperatorActivation::begin`] each time an
+/// operator is scheduled, and use the returned handle to record hydration,
+/// yielding, and inflight-byte observations for the duration of that
+/// activation. Recording into the structured metrics report tree is gated
+/// on `ENABLE_OPERATOR_METRICS_REPORT`: when the dyncfg is unset, `begin`
+/// is a no-op and the observations are simply dropped on the floor.
+pub struct OperatorActivation {
+    report: Option<ReportScope>,
+}
+
+impl OperatorActivation {
+    /// Begins tracking an activation of `operator_id` within
+    /// `dataflow_id`, consulting `configs` for whether metrics collection
+    /// is currently enabled.
+    pub fn begin(
+        dataflow_id: usize,
+        operator_id: usize,
+        configs: &ConfigSet,
+    ) -> OperatorActivation {
+        let report = ENABLE_OPERATOR_METRICS_REPORT
+            .get(configs)
+            .then(|| ReportScope::operator(dataflow_id, operator_id));
+        OperatorActivation { report }
+    }
+
+    /// Records that the operator has finished hydrating.
+    pub fn record_hydrated(&mut self) {
+        if let Some(report) = &mut self.report {
+            report.record_hydrated(Utc::now());
+        }
+    }
+
+    /// Adds to the time this activation spent yielding under
+    /// `LINEAR_JOIN_YIELDING`.
+    pub fn record_yield(&mut self, duration: Duration) {
+        if let Some(report) = &mut self.report {
+            report.record_yield(duration);
+        }
+    }
+
+    /// Records an observed in-flight byte count, as governed by
+    /// `DATAFLOW_MAX_INFLIGHT_BYTES_CC`.
+    pub fn record_inflight_bytes(&mut self, bytes: usize) {
+        if let Some(report) = &mut self.report {
+            report.record_inflight_bytes(bytes);
+        }
+    }
+}
+
+/// Serializes the structured metrics report tree as JSON, if
+/// `ENABLE_OPERATOR_METRICS_REPORT` is set. Intended to be called on demand
+/// (e.g. from an introspection query) or at process exit.
+pub fn emit_metrics_report(configs: &ConfigSet) -> Option<serde_json::Result<String>> {
+    ENABLE_OPERATOR_METRICS_REPORT.get(configs).then(to_json)
+}
@@ -13,6 +13,7 @@ use std::net::IpAddr;
 use std::num::NonZeroUsize;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytesize::ByteSize;
@@ -22,6 +23,8 @@ use futures_core::stream::BoxStream;
 use serde::de::Unexpected;
 use serde::{Deserialize, Deserializer, Serialize};
 
+pub mod telemetry;
+
 /// An orchestrator manages services.
 ///
 /// A service is a set of one or more processes running the same image. See
@@ -74,7 +77,16 @@ pub struct ServiceEvent {
 }
 
 /// Describes the status of an orchestrated service.
-#[derive(Debug, Clone, Copy, Serialize)]
+///
+/// BREAKING CHANGE: this enum is no longer `Copy`, because
+/// [`ServiceStatus::Degraded`] carries owned `String`s. Any consumer that
+/// currently copies a `ServiceStatus` by value (e.g.
+/// `let status = *status_ref;`, or a struct deriving `Copy` that embeds
+/// one) will fail to compile and needs to
+/// switch to `.clone()`. No such call sites exist in this trimmed crate,
+/// but they are expected to exist in the full repository and should be
+/// audited and updated when this change lands there.
+#[derive(Debug, Clone, Serialize)]
 pub enum ServiceStatus {
     /// Service is ready to accept requests.
     Ready,
@@ -82,6 +94,20 @@ pub enum ServiceStatus {
     NotReady,
     /// Service status is unknown.
     Unknown,
+    /// Service is up but failing one of its configured probes.
+    ///
+    /// This is distinct from [`ServiceStatus::NotReady`], which orchestrator
+    /// backends use while a service has not yet started; `Degraded`
+    /// specifically indicates a process that started but is now failing
+    /// liveness/readiness checks (e.g. crash-looping), rather than one that
+    /// is merely slow to warm up.
+    Degraded {
+        /// The name of the probe that is failing, as given in
+        /// [`ProbeConfig::name`].
+        probe: String,
+        /// A description of the most recent probe failure.
+        last_error: String,
+    },
 }
 
 /// Describes a running service managed by an `Orchestrator`.
@@ -121,6 +147,128 @@ pub struct ServiceConfig<'a> {
     /// The availability zone the service should be run in. If no availability
     /// zone is specified, the orchestrator is free to choose one.
     pub availability_zone: Option<String>,
+    /// An optional liveness/readiness probe. If set, orchestrator
+    /// implementations should evaluate it to drive the `ServiceStatus`
+    /// transitions surfaced through `watch_services`, rather than relying on
+    /// their own readiness heuristic.
+    pub probe: Option<ProbeConfig>,
+}
+
+/// Describes a liveness/readiness probe for a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeConfig {
+    /// A descriptive name for the probe, surfaced in
+    /// [`ServiceStatus::Degraded`] when it fails.
+    pub name: String,
+    /// How the probe determines whether the service is healthy.
+    pub kind: ProbeKind,
+    /// How long to wait after the service starts before evaluating the
+    /// probe for the first time.
+    pub initial_delay: Duration,
+    /// How often to evaluate the probe.
+    pub period: Duration,
+    /// How long to wait for a single probe evaluation before considering it
+    /// a failure.
+    pub timeout: Duration,
+    /// The number of consecutive successful evaluations required to
+    /// transition from failing to healthy.
+    pub success_threshold: u32,
+    /// The number of consecutive failed evaluations required to transition
+    /// from healthy to [`ServiceStatus::Degraded`].
+    pub failure_threshold: u32,
+}
+
+/// The mechanism by which a [`ProbeConfig`] determines service health.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeKind {
+    /// Attempt a TCP connection to the named [`ServicePort`].
+    Tcp {
+        /// The name of the port to connect to, as given in
+        /// [`ServiceConfig::ports`].
+        port_name: String,
+    },
+    /// Issue an HTTP `GET` request and consider any `2xx` response healthy.
+    Http {
+        /// The name of the port to issue the request against.
+        port_name: String,
+        /// The path to request, e.g. `/healthz`.
+        path: String,
+    },
+    /// Run a command inside the service's process/container and consider a
+    /// zero exit status healthy.
+    Exec {
+        /// The command to run.
+        command: String,
+        /// Arguments to the command.
+        args: Vec<String>,
+    },
+}
+
+/// Evaluates repeated probe results against a [`ProbeConfig`]'s thresholds
+/// to decide when a service's [`ServiceStatus`] should transition to or
+/// from [`ServiceStatus::Degraded`].
+///
+/// `NamespacedOrchestrator` implementations are expected to poll each
+/// service's configured probe on its `period` (respecting `initial_delay`
+/// and `timeout`), feed the outcome of each poll through
+/// [`ProbeEvaluator::record`], and surface the result (when `Some`) through
+/// `watch_services`. No backend in this crate runs probes yet; this is the
+/// shared threshold state machine they should use when they do, so that
+/// "how many consecutive failures count as degraded" isn't reinvented per
+/// backend.
+#[derive(Debug, Clone)]
+pub struct ProbeEvaluator {
+    probe_name: String,
+    success_threshold: u32,
+    failure_threshold: u32,
+    consecutive_successes: u32,
+    consecutive_failures: u32,
+    degraded: bool,
+}
+
+impl ProbeEvaluator {
+    /// Creates an evaluator for `probe`, starting in the healthy state.
+    pub fn new(probe: &ProbeConfig) -> ProbeEvaluator {
+        ProbeEvaluator {
+            probe_name: probe.name.clone(),
+            success_threshold: probe.success_threshold,
+            failure_threshold: probe.failure_threshold,
+            consecutive_successes: 0,
+            consecutive_failures: 0,
+            degraded: false,
+        }
+    }
+
+    /// Records the outcome of a single probe evaluation, returning the new
+    /// [`ServiceStatus`] if this observation caused a transition, or `None`
+    /// if the status should remain unchanged.
+    pub fn record(&mut self, result: Result<(), String>) -> Option<ServiceStatus> {
+        match result {
+            Ok(()) => {
+                self.consecutive_successes += 1;
+                self.consecutive_failures = 0;
+                if self.degraded && self.consecutive_successes >= self.success_threshold {
+                    self.degraded = false;
+                    Some(ServiceStatus::Ready)
+                } else {
+                    None
+                }
+            }
+            Err(last_error) => {
+                self.consecutive_failures += 1;
+                self.consecutive_successes = 0;
+                if !self.degraded && self.consecutive_failures >= self.failure_threshold {
+                    self.degraded = true;
+                    Some(ServiceStatus::Degraded {
+                        probe: self.probe_name.clone(),
+                        last_error,
+                    })
+                } else {
+                    None
+                }
+            }
+        }
+    }
 }
 
 /// A named port associated with a service.
@@ -232,3 +380,54 @@ impl Serialize for CpuLimit {
         <f64 as Serialize>::serialize(&(self.millicpus as f64 / 1000.0), serializer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe() -> ProbeConfig {
+        ProbeConfig {
+            name: "http".into(),
+            kind: ProbeKind::Http {
+                port_name: "http".into(),
+                path: "/healthz".into(),
+            },
+            initial_delay: Duration::ZERO,
+            period: Duration::from_secs(1),
+            timeout: Duration::from_secs(1),
+            success_threshold: 2,
+            failure_threshold: 3,
+        }
+    }
+
+    #[mz_ore::test]
+    fn test_probe_evaluator_degrades_after_threshold_failures() {
+        let probe = probe();
+        let mut evaluator = ProbeEvaluator::new(&probe);
+
+        assert!(evaluator.record(Err("connection refused".into())).is_none());
+        assert!(evaluator.record(Err("connection refused".into())).is_none());
+        match evaluator.record(Err("connection refused".into())) {
+            Some(ServiceStatus::Degraded { probe, last_error }) => {
+                assert_eq!(probe, "http");
+                assert_eq!(last_error, "connection refused");
+            }
+            other => panic!("expected Degraded, got {other:?}"),
+        }
+    }
+
+    #[mz_ore::test]
+    fn test_probe_evaluator_recovers_after_threshold_successes() {
+        let probe = probe();
+        let mut evaluator = ProbeEvaluator::new(&probe);
+        for _ in 0..probe.failure_threshold {
+            evaluator.record(Err("boom".into()));
+        }
+
+        assert!(evaluator.record(Ok(())).is_none());
+        assert!(matches!(
+            evaluator.record(Ok(())),
+            Some(ServiceStatus::Ready)
+        ));
+    }
+}
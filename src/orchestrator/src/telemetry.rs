@@ -0,0 +1,350 @@
+// Copyright Materialize, Inc. and contributors. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! A background dispatcher for orchestrator telemetry.
+//!
+//! [`NamespacedOrchestrator`] implementations each track [`ServiceEvent`]s and
+//! resource usage in their own way. This module gives them a single,
+//! reusable path for turning those observations into batched reports that
+//! are submitted to a pluggable [`TelemetrySink`], so that backends don't
+//! need to reinvent buffering, backpressure, or flushing.
+//!
+//! The dispatcher enqueues typed [`Task`]s onto a single bounded FIFO queue
+//! and drains them in order on one background worker. Submissions made
+//! before a sink has been wired up (via [`TelemetryDispatcher::set_sink`])
+//! are buffered rather than discarded. If the queue fills up, the oldest
+//! entry is dropped to make room for the newest, and
+//! [`TelemetryDispatcher::dropped`] is incremented so operators can notice
+//! they're falling behind.
+//!
+//! [`NamespacedOrchestrator`]: crate::NamespacedOrchestrator
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::{oneshot, Notify};
+
+use crate::{CpuLimit, MemoryLimit, ServiceEvent};
+
+/// The maximum number of telemetry tasks that may sit in the dispatcher's
+/// queue before the oldest entry is dropped to make room for a new one.
+const QUEUE_CAPACITY: usize = 100;
+
+/// A periodic sample of a service's configured resource limits.
+///
+/// This is a snapshot of the *limits* a service is configured with, rather
+/// than live usage, since the orchestrator abstraction has no uniform way
+/// to observe actual consumption across backends.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceUsageSample {
+    pub service_id: String,
+    pub memory_limit: Option<MemoryLimit>,
+    pub cpu_limit: Option<CpuLimit>,
+    pub time: DateTime<Utc>,
+}
+
+/// A batch of telemetry observations submitted to a [`TelemetrySink`] in a
+/// single call.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TelemetryReport {
+    pub service_events: Vec<ServiceEvent>,
+    pub resource_samples: Vec<ResourceUsageSample>,
+}
+
+impl TelemetryReport {
+    fn is_empty(&self) -> bool {
+        self.service_events.is_empty() && self.resource_samples.is_empty()
+    }
+}
+
+/// A pluggable destination for [`TelemetryReport`]s.
+///
+/// Implementations might ship reports to an external telemetry service,
+/// write them to disk, or (in tests) simply record them for later
+/// inspection.
+#[async_trait]
+pub trait TelemetrySink: std::fmt::Debug + Send + Sync {
+    /// Submits a report. The dispatcher awaits this call before draining
+    /// further tasks, so implementations should not block indefinitely.
+    async fn submit_report(&self, report: TelemetryReport);
+}
+
+/// A single unit of work enqueued on a [`TelemetryDispatcher`].
+enum Task {
+    ServiceEvent(ServiceEvent),
+    ResourceUsageSample(ResourceUsageSample),
+    /// A barrier requested via [`TelemetryDispatcher::flush`]. Once every
+    /// task enqueued before the barrier has been processed, `submit_report`
+    /// (if a sink is present) resolves and the sender is notified.
+    Flush(oneshot::Sender<()>),
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Task>>,
+    notify: Notify,
+    sink: Mutex<Option<Arc<dyn TelemetrySink>>>,
+    pending: Mutex<TelemetryReport>,
+    dropped: AtomicU64,
+    test_mode: bool,
+}
+
+/// Dispatches orchestrator telemetry to a pluggable [`TelemetrySink`].
+///
+/// Events are enqueued with [`record_service_event`](Self::record_service_event)
+/// and [`record_resource_usage`](Self::record_resource_usage). In normal
+/// operation these calls return immediately and a background task drains
+/// the queue, batching tasks into [`TelemetryReport`]s and submitting them
+/// to the configured sink. Constructing with [`TelemetryDispatcher::new_test`]
+/// instead leaves enqueued tasks undrained until the test explicitly awaits
+/// [`block_until_idle`](Self::block_until_idle), [`flush`](Self::flush), or
+/// [`set_sink`](Self::set_sink), since there is no
+/// background worker to race with; this lets tests assert on the exact
+/// sequence of recorded events.
+#[derive(Clone)]
+pub struct TelemetryDispatcher {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for TelemetryDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TelemetryDispatcher")
+            .field("dropped", &self.dropped())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TelemetryDispatcher {
+    /// Creates a dispatcher that drains its queue on a background
+    /// `tokio` task.
+    pub fn new() -> TelemetryDispatcher {
+        Self::new_inner(false)
+    }
+
+    /// Creates a dispatcher with no background worker. Tasks sit in the
+    /// queue until explicitly drained via [`block_until_idle`](Self::block_until_idle),
+    /// [`flush`](Self::flush), or [`set_sink`](Self::set_sink). Intended for
+    /// use in tests, where the absence of a concurrent drainer lets tests
+    /// assert on the exact sequence of recorded events.
+    pub fn new_test() -> TelemetryDispatcher {
+        Self::new_inner(true)
+    }
+
+    fn new_inner(test_mode: bool) -> TelemetryDispatcher {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            sink: Mutex::new(None),
+            pending: Mutex::new(TelemetryReport::default()),
+            dropped: AtomicU64::new(0),
+            test_mode,
+        });
+        if !test_mode {
+            let shared = Arc::clone(&shared);
+            mz_ore::task::spawn(|| "telemetry_dispatcher", async move {
+                Self::run(shared).await;
+            });
+        }
+        TelemetryDispatcher { shared }
+    }
+
+    /// Wires up the sink that reports are submitted to. Any tasks enqueued
+    /// (and not yet dropped for capacity reasons) before this call are
+    /// flushed to the new sink.
+    pub async fn set_sink(&self, sink: Arc<dyn TelemetrySink>) {
+        *self.shared.sink.lock().expect("lock poisoned") = Some(sink);
+        self.enqueue(Task::Flush(oneshot::channel().0));
+        if self.shared.test_mode {
+            self.drain_once().await;
+        } else {
+            self.shared.notify.notify_one();
+        }
+    }
+
+    /// Enqueues a [`ServiceEvent`] for submission to the sink.
+    pub fn record_service_event(&self, event: ServiceEvent) {
+        self.enqueue(Task::ServiceEvent(event));
+    }
+
+    /// Enqueues a [`ResourceUsageSample`] for submission to the sink.
+    pub fn record_resource_usage(&self, sample: ResourceUsageSample) {
+        self.enqueue(Task::ResourceUsageSample(sample));
+    }
+
+    /// Returns a future that resolves once every task enqueued before this
+    /// call has been processed (and, if a sink is present, submitted).
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        self.enqueue(Task::Flush(tx));
+        if self.shared.test_mode {
+            self.drain_once().await;
+        }
+        // The sender may have been dropped if the dispatcher's task was torn
+        // down; treat that the same as a completed flush.
+        let _ = rx.await;
+    }
+
+    /// In test mode, awaits the processing of every previously enqueued
+    /// task. Outside of test mode this is a no-op, since ordering with the
+    /// background worker cannot be observed from the caller.
+    pub async fn block_until_idle(&self) {
+        if self.shared.test_mode {
+            self.drain_once().await;
+        }
+    }
+
+    /// The number of tasks dropped so far because the queue was at
+    /// capacity.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    fn enqueue(&self, task: Task) {
+        let mut queue = self.shared.queue.lock().expect("lock poisoned");
+        if queue.len() >= QUEUE_CAPACITY {
+            queue.pop_front();
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queue.push_back(task);
+        drop(queue);
+        if !self.shared.test_mode {
+            self.shared.notify.notify_one();
+        }
+    }
+
+    /// Drains every task currently in the queue. Only valid to call in test
+    /// mode, where there is no concurrent background worker racing to drain
+    /// the same queue.
+    async fn drain_once(&self) {
+        while let Some(task) = {
+            let mut queue = self.shared.queue.lock().expect("lock poisoned");
+            queue.pop_front()
+        } {
+            Self::process(&self.shared, task).await;
+        }
+    }
+
+    async fn run(shared: Arc<Shared>) {
+        loop {
+            let task = {
+                let mut queue = shared.queue.lock().expect("lock poisoned");
+                queue.pop_front()
+            };
+            match task {
+                Some(task) => Self::process(&shared, task).await,
+                None => shared.notify.notified().await,
+            }
+        }
+    }
+
+    async fn process(shared: &Shared, task: Task) {
+        match task {
+            Task::ServiceEvent(event) => {
+                shared
+                    .pending
+                    .lock()
+                    .expect("lock poisoned")
+                    .service_events
+                    .push(event);
+            }
+            Task::ResourceUsageSample(sample) => {
+                shared
+                    .pending
+                    .lock()
+                    .expect("lock poisoned")
+                    .resource_samples
+                    .push(sample);
+            }
+            Task::Flush(tx) => {
+                let sink = shared.sink.lock().expect("lock poisoned").clone();
+                if let Some(sink) = sink {
+                    let report =
+                        std::mem::take(&mut *shared.pending.lock().expect("lock poisoned"));
+                    if !report.is_empty() {
+                        sink.submit_report(report).await;
+                    }
+                }
+                // The receiver may already have been dropped (e.g. the
+                // internal flush enqueued by `set_sink`); that's fine.
+                let _ = tx.send(());
+            }
+        }
+    }
+}
+
+impl Default for TelemetryDispatcher {
+    fn default() -> TelemetryDispatcher {
+        TelemetryDispatcher::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct RecordingSink {
+        reports: StdMutex<Vec<TelemetryReport>>,
+    }
+
+    #[async_trait]
+    impl TelemetrySink for RecordingSink {
+        async fn submit_report(&self, report: TelemetryReport) {
+            self.reports.lock().expect("lock poisoned").push(report);
+        }
+    }
+
+    fn event(service_id: &str) -> ServiceEvent {
+        ServiceEvent {
+            service_id: service_id.into(),
+            process_id: 0,
+            status: crate::ServiceStatus::Ready,
+            time: Utc::now(),
+        }
+    }
+
+    #[mz_ore::test(tokio::test)]
+    async fn test_buffers_before_sink_wired() {
+        let dispatcher = TelemetryDispatcher::new_test();
+        dispatcher.record_service_event(event("a"));
+        dispatcher.record_service_event(event("b"));
+        dispatcher.block_until_idle().await;
+
+        let sink = Arc::new(RecordingSink::default());
+        dispatcher.set_sink(sink.clone()).await;
+
+        let reports = sink.reports.lock().expect("lock poisoned");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].service_events.len(), 2);
+        assert_eq!(reports[0].service_events[0].service_id, "a");
+        assert_eq!(reports[0].service_events[1].service_id, "b");
+    }
+
+    #[mz_ore::test]
+    fn test_drops_oldest_on_overflow() {
+        let dispatcher = TelemetryDispatcher::new_test();
+        for i in 0..(QUEUE_CAPACITY + 10) {
+            dispatcher.record_service_event(event(&i.to_string()));
+        }
+        assert_eq!(dispatcher.dropped(), 10);
+
+        // Events "0" through "9" were the oldest, so "10" is now at the front.
+        let queue = dispatcher.shared.queue.lock().expect("lock poisoned");
+        assert_eq!(queue.len(), QUEUE_CAPACITY);
+        match queue.front() {
+            Some(Task::ServiceEvent(event)) => assert_eq!(event.service_id, "10"),
+            _ => panic!("expected a service event at the front of the queue"),
+        }
+    }
+}